@@ -0,0 +1,101 @@
+use std::sync::Arc;
+
+use serenity::all::{
+    CommandInteraction, Context, CreateEmbed, CreateInteractionResponse,
+    CreateInteractionResponseMessage,
+};
+
+use super::{worker::WorkerControl, RuntimeState, ServiceManager};
+
+/// Handles the `/workers` admin command: lists every registered service
+/// and whether its background task is active, idle, or dead.
+pub async fn handle_list_workers(
+    ctx: &Context,
+    interaction: &CommandInteraction,
+    service_manager: Arc<ServiceManager>,
+) -> Result<(), serenity::Error> {
+    let snapshots = service_manager.service_snapshots().await;
+
+    let mut embed = CreateEmbed::new().title("Workers");
+    for snapshot in snapshots {
+        let emoji = match snapshot.runtime_state {
+            RuntimeState::Active => "🟢",
+            RuntimeState::Idle => "🟡",
+            RuntimeState::Dead(_) => "🔴",
+        };
+
+        embed = embed.field(
+            format!("{} {}", emoji, snapshot.name),
+            format!("`{}` — {}", snapshot.id, snapshot.runtime_state),
+            false,
+        );
+    }
+
+    interaction
+        .create_response(
+            &ctx.http,
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new().embed(embed),
+            ),
+        )
+        .await
+}
+
+/// Handles the `/worker pause <id>` admin command: tells the named
+/// service's worker loop to stop calling `work` until it's resumed.
+pub async fn handle_pause_worker(
+    ctx: &Context,
+    interaction: &CommandInteraction,
+    service_manager: Arc<ServiceManager>,
+) -> Result<(), serenity::Error> {
+    respond_to_control(ctx, interaction, service_manager, WorkerControl::Pause, "paused").await
+}
+
+/// Handles the `/worker resume <id>` admin command: tells the named
+/// service's worker loop to leave the paused state and resume normal work.
+pub async fn handle_resume_worker(
+    ctx: &Context,
+    interaction: &CommandInteraction,
+    service_manager: Arc<ServiceManager>,
+) -> Result<(), serenity::Error> {
+    respond_to_control(ctx, interaction, service_manager, WorkerControl::Resume, "resumed").await
+}
+
+async fn respond_to_control(
+    ctx: &Context,
+    interaction: &CommandInteraction,
+    service_manager: Arc<ServiceManager>,
+    control: WorkerControl,
+    action: &str,
+) -> Result<(), serenity::Error> {
+    let Some(id) = interaction
+        .data
+        .options
+        .first()
+        .and_then(|option| option.value.as_str())
+    else {
+        return interaction
+            .create_response(
+                &ctx.http,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new().content("Missing `id` option."),
+                ),
+            )
+            .await;
+    };
+
+    let message = if service_manager.send_control_by_id(id, control).await {
+        format!("`{}` {}.", id, action)
+    } else {
+        format!("No running worker found for `{}`.", id)
+    };
+
+    interaction
+        .create_response(
+            &ctx.http,
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new().content(message),
+            ),
+        )
+        .await
+}
@@ -0,0 +1,52 @@
+use std::sync::Arc;
+
+use serenity::all::Context;
+
+use crate::setlock::SetLock;
+
+use super::{PinnedBoxedFutureResult, Priority, Service, ServiceInfo, ServiceManager};
+
+pub struct DiscordService {
+    info: ServiceInfo,
+    context: SetLock<Context>,
+}
+
+impl DiscordService {
+    pub fn new() -> Self {
+        Self {
+            info: ServiceInfo::new("lum_builtin_discord", "Discord", Priority::Critical),
+            context: SetLock::new(),
+        }
+    }
+
+    /// The serenity [`Context`] captured once the gateway `ready` event
+    /// fires. Other services read through this instead of each keeping
+    /// their own copy.
+    pub fn context(&self) -> &Context {
+        self.context.unwrap()
+    }
+
+    pub fn set_context(&self, context: Context) -> Result<(), crate::setlock::SetLockError> {
+        self.context.set(context)
+    }
+}
+
+impl Default for DiscordService {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Service for DiscordService {
+    fn info(&self) -> &ServiceInfo {
+        &self.info
+    }
+
+    fn start(&mut self, _service_manager: Arc<ServiceManager>) -> PinnedBoxedFutureResult<'_, ()> {
+        Box::pin(async move { Ok(()) })
+    }
+
+    fn stop(&mut self) -> PinnedBoxedFutureResult<'_, ()> {
+        Box::pin(async move { Ok(()) })
+    }
+}
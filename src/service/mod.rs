@@ -0,0 +1,452 @@
+pub mod admin_command;
+pub mod discord;
+pub mod osu_mute;
+pub mod worker;
+
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    error::Error,
+    fmt::{self, Display, Formatter},
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use rand::Rng;
+use tokio::{
+    sync::{mpsc, RwLock},
+    time::sleep,
+};
+
+use self::worker::{WorkerControl, WorkerState};
+
+pub type PinnedBoxedFutureResult<'a, T> =
+    Pin<Box<dyn Future<Output = Result<T, Box<dyn Error + Send + Sync>>> + Send + 'a>>;
+
+/// A [`ServiceInfo::id`], used to declare dependencies between services.
+pub type ServiceId = &'static str;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Priority {
+    Critical,
+    Optional,
+}
+
+#[derive(Debug, Clone)]
+pub enum Status {
+    Stopped,
+    Starting,
+    Started,
+    RuntimeError(String),
+}
+
+impl Display for Status {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Stopped => write!(f, "Stopped"),
+            Self::Starting => write!(f, "Starting"),
+            Self::Started => write!(f, "Started"),
+            Self::RuntimeError(error) => write!(f, "RuntimeError: {}", error),
+        }
+    }
+}
+
+pub struct ServiceInfo {
+    pub id: &'static str,
+    pub name: &'static str,
+    pub priority: Priority,
+    pub status: RwLock<Status>,
+    pub worker_state: RwLock<Option<WorkerState>>,
+    pub restart_policy: RestartPolicy,
+    /// Set by the supervisor while the worker loop is running; lets any
+    /// caller holding this `ServiceInfo` send `Pause`/`Resume`/`Cancel`
+    /// without reaching for the concrete service type.
+    pub control: RwLock<Option<mpsc::UnboundedSender<WorkerControl>>>,
+    /// Services that must reach `Status::Started` before `ServiceManager`
+    /// will call `start()` on this one.
+    pub requires: &'static [ServiceId],
+}
+
+impl ServiceInfo {
+    pub fn new(id: &'static str, name: &'static str, priority: Priority) -> Self {
+        Self {
+            id,
+            name,
+            priority,
+            status: RwLock::new(Status::Stopped),
+            worker_state: RwLock::new(None),
+            restart_policy: RestartPolicy::default_for(priority),
+            control: RwLock::new(None),
+            requires: &[],
+        }
+    }
+
+    pub fn with_restart_policy(mut self, restart_policy: RestartPolicy) -> Self {
+        self.restart_policy = restart_policy;
+        self
+    }
+
+    pub fn with_requires(mut self, requires: &'static [ServiceId]) -> Self {
+        self.requires = requires;
+        self
+    }
+
+    /// Sends a lifecycle command to the running worker loop, if one is
+    /// currently supervising this service.
+    pub async fn send_control(&self, control: WorkerControl) -> bool {
+        match self.control.read().await.as_ref() {
+            Some(control_tx) => control_tx.send(control).is_ok(),
+            None => false,
+        }
+    }
+}
+
+/// How a crashed service's background task should be supervised.
+#[derive(Debug, Clone)]
+pub enum RestartPolicy {
+    /// Leave the service in `Status::RuntimeError`; an operator has to
+    /// intervene. The right default for services whose failure should be
+    /// escalated rather than papered over.
+    Never,
+    /// Restart automatically with exponential backoff plus jitter.
+    OnFailure {
+        base_delay: Duration,
+        max_delay: Duration,
+        /// How long the task must stay up before `consecutive_failures`
+        /// resets to 0.
+        stable_window: Duration,
+        /// Give up and leave the service `RuntimeError` after this many
+        /// restarts without a stable run in between.
+        max_restarts: u32,
+    },
+}
+
+impl RestartPolicy {
+    /// `Optional` services restart themselves; `Critical` ones escalate so a
+    /// human notices instead of flapping silently in the background.
+    pub fn default_for(priority: Priority) -> Self {
+        match priority {
+            Priority::Optional => Self::OnFailure {
+                base_delay: Duration::from_secs(1),
+                max_delay: Duration::from_secs(60),
+                stable_window: Duration::from_secs(60),
+                max_restarts: 5,
+            },
+            Priority::Critical => Self::Never,
+        }
+    }
+}
+
+pub trait Service: Send + Sync {
+    fn info(&self) -> &ServiceInfo;
+    fn start(&mut self, service_manager: Arc<ServiceManager>) -> PinnedBoxedFutureResult<'_, ()>;
+    fn stop(&mut self) -> PinnedBoxedFutureResult<'_, ()>;
+}
+
+/// Owns every registered [`Service`] and lets callers look services up by
+/// concrete type (`get_service`) or enumerate all of them regardless of
+/// type (`services`).
+#[derive(Default)]
+pub struct ServiceManager {
+    by_type: RwLock<HashMap<TypeId, Box<dyn Any + Send + Sync>>>,
+    registry: RwLock<Vec<Arc<RwLock<dyn Service>>>>,
+    restart_states: RwLock<HashMap<&'static str, RestartState>>,
+}
+
+struct RestartState {
+    consecutive_failures: u32,
+    last_started_at: Instant,
+}
+
+impl ServiceManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub async fn register<T>(&self, service: T) -> Arc<RwLock<T>>
+    where
+        T: Service + 'static,
+    {
+        let service = Arc::new(RwLock::new(service));
+
+        self.by_type
+            .write()
+            .await
+            .insert(TypeId::of::<T>(), Box::new(Arc::clone(&service)));
+        self.registry
+            .write()
+            .await
+            .push(Arc::clone(&service) as Arc<RwLock<dyn Service>>);
+
+        service
+    }
+
+    pub async fn get_service<T>(&self) -> Option<Arc<RwLock<T>>>
+    where
+        T: 'static,
+    {
+        self.by_type
+            .read()
+            .await
+            .get(&TypeId::of::<T>())?
+            .downcast_ref::<Arc<RwLock<T>>>()
+            .cloned()
+    }
+
+    /// All registered services, in registration order, regardless of type.
+    pub async fn services(&self) -> Vec<Arc<RwLock<dyn Service>>> {
+        self.registry.read().await.clone()
+    }
+
+    /// A point-in-time snapshot of every registered service, for
+    /// introspection (e.g. an admin command listing running workers).
+    pub async fn service_snapshots(&self) -> Vec<ServiceSnapshot> {
+        let mut snapshots = Vec::new();
+
+        for service in self.registry.read().await.iter() {
+            let service = service.read().await;
+            let info = service.info();
+
+            let status = info.status.read().await;
+            let runtime_state = match &*status {
+                Status::RuntimeError(error) => RuntimeState::Dead(error.clone()),
+                _ => match *info.worker_state.read().await {
+                    Some(WorkerState::Idle) => RuntimeState::Idle,
+                    _ => RuntimeState::Active,
+                },
+            };
+
+            snapshots.push(ServiceSnapshot {
+                id: info.id,
+                name: info.name,
+                priority: info.priority,
+                status: status.clone(),
+                runtime_state,
+            });
+        }
+
+        snapshots
+    }
+
+    /// Records that a service's background task just (re)started, so a
+    /// later failure can tell whether it ran long enough to count as
+    /// stable.
+    pub async fn note_started(&self, id: &'static str) {
+        let mut states = self.restart_states.write().await;
+        let state = states.entry(id).or_insert(RestartState {
+            consecutive_failures: 0,
+            last_started_at: Instant::now(),
+        });
+        state.last_started_at = Instant::now();
+    }
+
+    /// Called when a service's task has failed. Returns the delay to wait
+    /// before restarting, or `None` if the policy says to give up and leave
+    /// the service `RuntimeError`.
+    pub async fn note_failure(&self, info: &ServiceInfo) -> Option<Duration> {
+        let RestartPolicy::OnFailure {
+            base_delay,
+            max_delay,
+            stable_window,
+            max_restarts,
+        } = &info.restart_policy
+        else {
+            return None;
+        };
+
+        let mut states = self.restart_states.write().await;
+        let state = states.entry(info.id).or_insert(RestartState {
+            consecutive_failures: 0,
+            last_started_at: Instant::now(),
+        });
+
+        if state.last_started_at.elapsed() >= *stable_window {
+            state.consecutive_failures = 0;
+        }
+
+        if state.consecutive_failures >= *max_restarts {
+            return None;
+        }
+
+        let backoff_factor = 1u32.checked_shl(state.consecutive_failures).unwrap_or(u32::MAX);
+        let delay = base_delay.saturating_mul(backoff_factor).min(*max_delay);
+        let max_jitter_millis = (delay.as_millis() / 2) as u64;
+        let jitter = Duration::from_millis(rand::thread_rng().gen_range(0..=max_jitter_millis));
+
+        state.consecutive_failures += 1;
+
+        Some(delay + jitter)
+    }
+
+    /// Sends a lifecycle command to the registered service with the given
+    /// id, if one exists. Returns `false` if no service is registered under
+    /// `id`, or its worker loop isn't currently running to receive it.
+    pub async fn send_control_by_id(&self, id: &str, control: WorkerControl) -> bool {
+        for service in self.registry.read().await.iter() {
+            let service = service.read().await;
+            if service.info().id == id {
+                return service.info().send_control(control).await;
+            }
+        }
+
+        false
+    }
+
+    /// Starts every registered service in dependency order (per
+    /// `ServiceInfo::requires`), waiting up to `readiness_timeout` for each
+    /// dependency to reach `Status::Started` before starting its dependents.
+    pub async fn start_all(
+        self: &Arc<Self>,
+        readiness_timeout: Duration,
+    ) -> Result<(), String> {
+        let services = self.registry.read().await.clone();
+
+        let mut by_id = HashMap::new();
+        for service in &services {
+            by_id.insert(service.read().await.info().id, Arc::clone(service));
+        }
+
+        for id in topological_order(&services).await? {
+            let service = by_id
+                .get(id)
+                .expect("id came from the registry we just walked");
+
+            let requires = service.read().await.info().requires;
+            for dependency_id in requires {
+                // `topological_order` already validated that every `requires`
+                // entry resolves to a registered service.
+                let dependency = by_id
+                    .get(dependency_id)
+                    .expect("topological_order validated requires against by_id's keys");
+
+                wait_until_started(dependency, readiness_timeout)
+                    .await
+                    .map_err(|_| {
+                        format!(
+                            "service {} timed out after {:?} waiting for dependency {} to start",
+                            id, readiness_timeout, dependency_id
+                        )
+                    })?;
+            }
+
+            service
+                .write()
+                .await
+                .start(Arc::clone(self))
+                .await
+                .map_err(|error| format!("failed to start service {}: {}", id, error))?;
+
+            *service.read().await.info().status.write().await = Status::Started;
+        }
+
+        Ok(())
+    }
+}
+
+/// Kahn's algorithm over `ServiceInfo::requires`, returning service ids in
+/// an order where every dependency comes before its dependents.
+async fn topological_order(
+    services: &[Arc<RwLock<dyn Service>>],
+) -> Result<Vec<ServiceId>, String> {
+    let mut requires_of = HashMap::new();
+    for service in services {
+        let service = service.read().await;
+        requires_of.insert(service.info().id, service.info().requires);
+    }
+
+    // Validate every dependency up front so an unregistered id is reported
+    // as exactly that, rather than surfacing as a false "cycle detected"
+    // once the Kahn's-algorithm loop below can never resolve it.
+    for (id, requires) in &requires_of {
+        for dependency_id in *requires {
+            if !requires_of.contains_key(dependency_id) {
+                return Err(format!(
+                    "service {} requires unknown service {}",
+                    id, dependency_id
+                ));
+            }
+        }
+    }
+
+    let mut resolved = Vec::new();
+    let mut remaining: Vec<ServiceId> = requires_of.keys().copied().collect();
+
+    while !remaining.is_empty() {
+        let ready_index = remaining.iter().position(|id| {
+            requires_of[id]
+                .iter()
+                .all(|dependency| resolved.contains(dependency))
+        });
+
+        match ready_index {
+            Some(index) => resolved.push(remaining.remove(index)),
+            None => {
+                return Err(format!(
+                    "service dependency cycle detected among: {}",
+                    remaining.join(", ")
+                ))
+            }
+        }
+    }
+
+    Ok(resolved)
+}
+
+/// Polls a service's status until it reaches `Status::Started` or
+/// `timeout` elapses.
+async fn wait_until_started(service: &Arc<RwLock<dyn Service>>, timeout: Duration) -> Result<(), ()> {
+    const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+    let deadline = Instant::now() + timeout;
+    loop {
+        if matches!(*service.read().await.info().status.read().await, Status::Started) {
+            return Ok(());
+        }
+
+        if Instant::now() >= deadline {
+            return Err(());
+        }
+
+        sleep(POLL_INTERVAL).await;
+    }
+}
+
+/// What a service's background worker is currently doing, derived from its
+/// `Status` and last reported `WorkerState`. A service whose loop has
+/// stopped keeps the state its worker last reported.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RuntimeState {
+    /// Neither `Idle` nor `Dead`: the worker loop is running and has work
+    /// to do, or the service has no worker state to report (no worker
+    /// loop, or none run yet), or its worker returned `WorkerState::Done`.
+    Active,
+    /// The worker's last turn returned `WorkerState::Idle`: nothing to do.
+    Idle,
+    /// The service is in `Status::RuntimeError`, set when the watchdog
+    /// fired or a restart attempt failed. A restart may still be pending
+    /// if its `RestartPolicy` allows one.
+    Dead(String),
+}
+
+impl Display for RuntimeState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Active => write!(f, "Active"),
+            Self::Idle => write!(f, "Idle"),
+            Self::Dead(error) => write!(f, "Dead ({})", error),
+        }
+    }
+}
+
+/// A point-in-time view of one registered service, returned by
+/// [`ServiceManager::service_snapshots`].
+#[derive(Debug, Clone)]
+pub struct ServiceSnapshot {
+    pub id: &'static str,
+    pub name: &'static str,
+    pub priority: Priority,
+    pub status: Status,
+    pub runtime_state: RuntimeState,
+}
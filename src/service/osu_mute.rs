@@ -3,44 +3,157 @@ use std::{
     error::Error,
     fmt::{self, Display, Formatter},
     sync::Arc,
-    time::Duration,
+    time::Instant,
 };
 
-use log::{error, info};
-use serenity::{
-    all::{GuildChannel, Member},
-    FutureExt,
-};
-use tokio::{
-    spawn,
-    sync::{Notify, RwLock},
-    task::JoinHandle,
-    time::sleep,
-};
+use log::error;
+use serenity::all::{Context, EditMember, GuildChannel, Member, UserId};
+use tokio::{sync::RwLock, task::JoinHandle};
 
 use crate::setlock::SetLock;
 
 use super::{
-    discord::DiscordService, PinnedBoxedFutureResult, Priority, Service, ServiceInfo,
-    ServiceManager, Status,
+    discord::DiscordService,
+    worker::{spawn_supervised, Worker, WorkerControl, WorkerCtx, WorkerState},
+    PinnedBoxedFutureResult, Priority, Service, ServiceInfo, ServiceManager, Status,
 };
 
+/// The exact activity name Discord reports for osu!.
+const OSU_ACTIVITY_NAME: &str = "osu!";
+
 pub struct OsuMuteService {
     info: ServiceInfo,
     discord_service: SetLock<Arc<RwLock<DiscordService>>>,
-    task_notify: Arc<RwLock<SetLock<Notify>>>,
     task: SetLock<JoinHandle<()>>,
-    pub muted_users: RwLock<HashMap<GuildChannel, Member>>,
+    /// Keyed by user rather than channel: a member is only ever in one
+    /// voice channel at a time, but a channel can hold several muted
+    /// members, and keying by channel would let a second muted member in
+    /// the same channel silently overwrite the first.
+    pub muted_users: RwLock<HashMap<UserId, (GuildChannel, Member)>>,
+    /// After every batch of mute/unmute calls, the worker reports a
+    /// `WorkerState::Throttled(elapsed * tranquility_factor)` backoff to
+    /// stay well clear of Discord's rate limits when many users change
+    /// state at once.
+    pub tranquility_factor: RwLock<f64>,
 }
 
 impl OsuMuteService {
     pub fn new() -> Self {
         Self {
-            info: ServiceInfo::new("lum_builtin_osu_mute", "osu! Mute", Priority::Optional),
+            info: ServiceInfo::new("lum_builtin_osu_mute", "osu! Mute", Priority::Optional)
+                .with_requires(&["lum_builtin_discord"]),
             discord_service: SetLock::new(),
-            task_notify: Arc::new(RwLock::new(SetLock::new())),
             task: SetLock::new(),
             muted_users: RwLock::new(HashMap::new()),
+            tranquility_factor: RwLock::new(1.0),
+        }
+    }
+
+    async fn osu_players(
+        &self,
+        ctx: &Context,
+    ) -> (Vec<(GuildChannel, Member)>, Vec<(GuildChannel, Member)>) {
+        let mut to_mute = Vec::new();
+
+        {
+            let already_muted = self.muted_users.read().await;
+
+            for guild_id in ctx.cache.guilds() {
+                let Some(guild) = ctx.cache.guild(guild_id) else {
+                    continue;
+                };
+
+                for (&user_id, presence) in guild.presences.iter() {
+                    let is_playing_osu = presence
+                        .activities
+                        .iter()
+                        .any(|activity| activity.name == OSU_ACTIVITY_NAME);
+                    if !is_playing_osu {
+                        continue;
+                    }
+
+                    let Some(channel_id) = guild
+                        .voice_states
+                        .get(&user_id)
+                        .and_then(|voice_state| voice_state.channel_id)
+                    else {
+                        continue;
+                    };
+                    let Some(channel) = guild.channels.get(&channel_id).cloned() else {
+                        continue;
+                    };
+                    if already_muted.contains_key(&user_id) {
+                        continue;
+                    }
+
+                    let Ok(member) = guild.member(ctx, user_id).await else {
+                        continue;
+                    };
+                    to_mute.push((channel, member.into_owned()));
+                }
+            }
+        }
+
+        let mut to_unmute = Vec::new();
+        for (channel, member) in self.muted_users.read().await.values() {
+            let still_playing_osu = ctx
+                .cache
+                .guild(channel.guild_id)
+                .and_then(|guild| {
+                    guild.presences.get(&member.user.id).map(|presence| {
+                        presence
+                            .activities
+                            .iter()
+                            .any(|activity| activity.name == OSU_ACTIVITY_NAME)
+                    })
+                })
+                .unwrap_or(false);
+
+            if !still_playing_osu {
+                to_unmute.push((channel.clone(), member.clone()));
+            }
+        }
+
+        (to_mute, to_unmute)
+    }
+
+    async fn mute(
+        &self,
+        ctx: &Context,
+        channel: &GuildChannel,
+        member: &Member,
+    ) -> serenity::Result<()> {
+        // Join the voice channel via songbird first; a failed join is
+        // ignored. The mute itself goes through the HTTP API below.
+        if let Some(songbird) = songbird::get(ctx).await {
+            let _ = songbird.join(channel.guild_id, channel.id).await;
+        }
+
+        channel
+            .guild_id
+            .edit_member(&ctx.http, member.user.id, EditMember::new().mute(true))
+            .await?;
+
+        Ok(())
+    }
+
+    async fn unmute(&self, ctx: &Context, member: &Member) -> serenity::Result<()> {
+        member
+            .guild_id
+            .edit_member(&ctx.http, member.user.id, EditMember::new().mute(false))
+            .await?;
+
+        Ok(())
+    }
+
+    /// Unmutes everyone this service currently has muted, e.g. so a
+    /// graceful `stop()` doesn't leave players permanently server-muted.
+    async fn unmute_all(&self, ctx: &Context) {
+        let muted_users: Vec<_> = self.muted_users.write().await.drain().collect();
+        for (_, (_, member)) in muted_users {
+            if let Err(error) = self.unmute(ctx, &member).await {
+                error!("Failed to unmute {} while stopping: {}", member.user.id, error);
+            }
         }
     }
 }
@@ -58,36 +171,26 @@ impl Service for OsuMuteService {
 
     fn start(&mut self, service_manager: Arc<ServiceManager>) -> PinnedBoxedFutureResult<'_, ()> {
         Box::pin(async move {
-            match service_manager.get_service::<DiscordService>().await {
-                Some(discord_service) => {
-                    if !discord_service.read().await.is_available().await {
-                        return Err("DiscordService is not available!".into());
-                    }
-
-                    let result = self.discord_service.set(discord_service.clone());
-                    if let Err(error) = result {
-                        return Err(
-                            format!("Error setting DiscordService SetLock: {}", error).into()
-                        );
-                    }
-                }
-                None => return Err("DiscordService not found!".into()),
-            }
+            // `ServiceInfo::requires` declares the dependency on
+            // `DiscordService`, so `ServiceManager::start_all` only calls
+            // `start()` here once it has already reached `Status::Started`.
+            let discord_service = service_manager
+                .get_service::<DiscordService>()
+                .await
+                .ok_or("DiscordService not found, even though it is a declared dependency!")?;
 
-            let result = self.task_notify.write().await.set(Notify::new());
-            if let Err(error) = result {
-                return Err(format!("Error setting Notify SetLock: {}", error).into());
-            }
+            // `replace`, not `set`: a restart after a crash calls `start()`
+            // again on this same instance, and these handles are only
+            // write-once *per start*, not for the service's whole lifetime.
+            self.discord_service.replace(discord_service);
 
-            let task = task(Arc::clone(&service_manager));
-            let task_with_watchdog = task
-                .then(|result| async move { watchdog(Arc::clone(&service_manager), result).await });
+            let service = match service_manager.get_service::<OsuMuteService>().await {
+                Some(service) => service,
+                None => return Err("OsuMuteService not found in its own ServiceManager!".into()),
+            };
 
-            let task_handle = spawn(task_with_watchdog);
-            let result = self.task.set(task_handle);
-            if let Err(error) = result {
-                return Err(format!("Error setting Watchdog JoinHandle SetLock: {}", error).into());
-            }
+            let task_handle = spawn_supervised(service, Arc::clone(&service_manager));
+            self.task.replace(task_handle);
 
             Ok(())
         })
@@ -95,22 +198,30 @@ impl Service for OsuMuteService {
 
     fn stop(&mut self) -> PinnedBoxedFutureResult<'_, ()> {
         Box::pin(async move {
-            self.task.unwrap().abort();
+            if !self.info.send_control(WorkerControl::Cancel).await {
+                // No supervisor is listening (e.g. it already crashed); fall
+                // back to a hard abort so `stop()` still takes effect.
+                self.task.unwrap().abort();
+            }
+
+            if let Some(discord_service) = self.discord_service.get() {
+                let ctx = discord_service.read().await.context().clone();
+                self.unmute_all(&ctx).await;
+            }
+
             Ok(())
         })
     }
 }
 
 #[derive(Debug)]
-enum TaskError {
-    DiscordServiceNotFound,
+enum OsuMuteWorkerError {
     DiscordServiceNotAvailable(String),
 }
 
-impl Display for TaskError {
+impl Display for OsuMuteWorkerError {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         match self {
-            Self::DiscordServiceNotFound => write!(f, "Discord service not found!"),
             Self::DiscordServiceNotAvailable(status) => write!(
                 f,
                 "Discord service expected to be available, but it was {}",
@@ -120,73 +231,53 @@ impl Display for TaskError {
     }
 }
 
-impl Error for TaskError {}
-
-async fn task(service_manager: Arc<ServiceManager>) -> Result<(), TaskError> {
-    let osu_mute_service = match service_manager.get_service::<OsuMuteService>().await {
-        Some(osu_mute_service) => osu_mute_service,
-        None => return Err(TaskError::DiscordServiceNotFound),
-    };
+impl Error for OsuMuteWorkerError {}
 
-    loop {
-        //TODO: When Rust allows async trait methods to be object-safe, refactor this to use service.is_available()
-        let osu_mute_service_lock = osu_mute_service.read().await;
-        let muted_users = osu_mute_service_lock.muted_users.read().await;
-
-        let are_users_muted = muted_users.is_empty();
-        drop(muted_users);
+impl Worker for OsuMuteService {
+    fn work<'a>(&'a mut self, _ctx: &'a WorkerCtx) -> PinnedBoxedFutureResult<'a, WorkerState> {
+        Box::pin(async move {
+            let discord_service = Arc::clone(self.discord_service.unwrap());
+            let discord_service_lock = discord_service.read().await;
 
-        if !are_users_muted {
-            osu_mute_service_lock
-                .task_notify
-                .read()
-                .await
-                .unwrap()
-                .notified()
-                .await;
-        }
+            let discord_service_status = discord_service_lock.info().status.read().await;
+            if !matches!(*discord_service_status, Status::Started) {
+                return Err(Box::new(OsuMuteWorkerError::DiscordServiceNotAvailable(
+                    discord_service_status.to_string(),
+                )) as Box<dyn Error + Send + Sync>);
+            }
+            drop(discord_service_status);
 
-        let discord_service = Arc::clone(osu_mute_service_lock.discord_service.unwrap());
-        let discord_service_lock = discord_service.read().await;
+            let discord_ctx = discord_service_lock.context().clone();
+            drop(discord_service_lock);
 
-        let discord_service_status = discord_service_lock.info().status.read().await;
-        if !matches!(*discord_service_status, Status::Started) {
-            return Err(TaskError::DiscordServiceNotAvailable(
-                discord_service_status.to_string(),
-            ));
-        }
-        drop(discord_service_status);
+            let (to_mute, to_unmute) = self.osu_players(&discord_ctx).await;
+            if to_mute.is_empty() && to_unmute.is_empty() {
+                return Ok(WorkerState::Idle);
+            }
 
-        //TODO: Add logic to mute users
-        sleep(Duration::from_secs(1)).await;
-        info!("Tick");
-    }
-}
+            let started = Instant::now();
 
-async fn watchdog(service_manager: Arc<ServiceManager>, result: Result<(), TaskError>) {
-    let osu_mute_service = match service_manager.get_service::<OsuMuteService>().await {
-        Some(osu_mute_service) => osu_mute_service,
-        None => panic!("Watchdog failed to get OsuMuteService"),
-    };
+            for (channel, member) in to_mute {
+                if let Err(error) = self.mute(&discord_ctx, &channel, &member).await {
+                    error!(
+                        "Failed to mute {} in #{}: {}",
+                        member.user.id, channel.name, error
+                    );
+                    continue;
+                }
+                let user_id = member.user.id;
+                self.muted_users.write().await.insert(user_id, (channel, member));
+            }
 
-    let osu_mute_service_lock = osu_mute_service.read().await;
-    let mut osu_mute_service_status = osu_mute_service_lock.info().status.write().await;
+            for (_channel, member) in to_unmute {
+                if let Err(error) = self.unmute(&discord_ctx, &member).await {
+                    error!("Failed to unmute {}: {}", member.user.id, error);
+                }
+                self.muted_users.write().await.remove(&member.user.id);
+            }
 
-    match result {
-        Ok(()) => {
-            *osu_mute_service_status =
-                Status::RuntimeError("The background task has stopped unexpectedly.".into());
-        }
-        Err(error) => {
-            *osu_mute_service_status = Status::RuntimeError(
-                format!("The background task has encountered an error: {}", error).into(),
-            );
-        }
+            let tranquility_factor = *self.tranquility_factor.read().await;
+            Ok(WorkerState::Throttled(started.elapsed().mul_f64(tranquility_factor)))
+        })
     }
-
-    error!(
-        "Watchdog triggered for service {}. {}",
-        osu_mute_service_lock.info().id,
-        osu_mute_service_status
-    );
 }
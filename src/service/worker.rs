@@ -0,0 +1,215 @@
+use std::{panic::AssertUnwindSafe, sync::Arc, time::Duration};
+
+use log::{error, info};
+use serenity::FutureExt;
+use tokio::{
+    select, spawn,
+    sync::{mpsc, RwLock},
+    task::JoinHandle,
+    time::sleep,
+};
+
+use super::{PinnedBoxedFutureResult, Service, ServiceManager, Status};
+
+/// Lifecycle commands a [`Service`]'s supervisor loop reacts to, on top of
+/// `start`/`stop`. Sent through [`ServiceInfo::control`][super::ServiceInfo].
+#[derive(Debug, Clone)]
+pub enum WorkerControl {
+    /// Stop calling `work` until `Resume`, without tearing the service down.
+    Pause,
+    /// Leave the paused state and resume calling `work`.
+    Resume,
+    /// Ask the worker loop to exit. The exit is clean only between `work` calls: if
+    /// no `work()` future is in flight when `Cancel` arrives, the loop
+    /// exits cleanly instead of being `abort()`ed. If `work()` *is* in
+    /// flight, `select!` drops it at its next await point exactly like
+    /// `abort()` would — `work()` gets no chance to flush state.
+    Cancel,
+}
+
+/// What a [`Worker`] did with its last turn of work.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WorkerState {
+    /// There is more work to do right away; call `work` again immediately.
+    Busy,
+    /// Did work, but the next call should wait out `Duration` first (e.g. to
+    /// stay clear of a rate limit). Unlike `Idle`'s fixed `IDLE_BACKOFF`,
+    /// this lets a `Worker` size its own backoff. The supervisor releases
+    /// its lock on the service before waiting, so the delay doesn't block
+    /// readers like `ServiceManager::service_snapshots`.
+    Throttled(Duration),
+    /// Nothing to do right now; the supervisor should back off before the
+    /// next call.
+    Idle,
+    /// The worker is finished for good; the supervisor loop exits cleanly.
+    Done,
+}
+
+/// Context handed to a [`Worker`] on every call to `work`.
+pub struct WorkerCtx {
+    pub service_manager: Arc<ServiceManager>,
+}
+
+/// A background loop that a [`Service`] implements instead of hand-rolling
+/// its own `task`/`watchdog` pair. `work` is called repeatedly by the
+/// supervisor spawned via [`spawn_supervised`] until it returns
+/// `WorkerState::Done` or an error.
+pub trait Worker: Service {
+    fn work<'a>(&'a mut self, ctx: &'a WorkerCtx) -> PinnedBoxedFutureResult<'a, WorkerState>;
+}
+
+const IDLE_BACKOFF: Duration = Duration::from_millis(250);
+
+/// Spawns `service`'s worker loop and wires it to a watchdog: a returned
+/// error, or a panic inside `work`, is turned into `Status::RuntimeError` on
+/// the service instead of being lost. If the service's `RestartPolicy`
+/// allows it, the watchdog also schedules a restart with backoff instead of
+/// leaving it dead.
+pub fn spawn_supervised<T>(
+    service: Arc<RwLock<T>>,
+    service_manager: Arc<ServiceManager>,
+) -> JoinHandle<()>
+where
+    T: Worker + 'static,
+{
+    spawn(supervise(service, service_manager))
+}
+
+async fn supervise<T>(service: Arc<RwLock<T>>, service_manager: Arc<ServiceManager>)
+where
+    T: Worker + 'static,
+{
+    let ctx = WorkerCtx {
+        service_manager: Arc::clone(&service_manager),
+    };
+
+    let (control_tx, mut control_rx) = mpsc::unbounded_channel();
+    {
+        let service_lock = service.read().await;
+        service_manager.note_started(service_lock.info().id).await;
+        *service_lock.info().control.write().await = Some(control_tx);
+    }
+
+    let mut paused = false;
+
+    loop {
+        if paused {
+            match control_rx.recv().await {
+                Some(WorkerControl::Resume) => paused = false,
+                Some(WorkerControl::Cancel) | None => break,
+                Some(WorkerControl::Pause) => {}
+            }
+            continue;
+        }
+
+        let mut service_lock = service.write().await;
+
+        let result = select! {
+            biased;
+
+            control = control_rx.recv() => {
+                match control {
+                    Some(WorkerControl::Pause) => {
+                        info!("Pausing service {}", service_lock.info().id);
+                        paused = true;
+                    }
+                    Some(WorkerControl::Resume) => {}
+                    Some(WorkerControl::Cancel) | None => {
+                        info!("Cancelling service {}", service_lock.info().id);
+                        break;
+                    }
+                }
+                continue;
+            }
+            result = AssertUnwindSafe(service_lock.work(&ctx)).catch_unwind() => result,
+        };
+
+        let result = match result {
+            Ok(result) => result,
+            Err(panic) => Err(format!("task panicked: {}", panic_message(panic.as_ref())).into()),
+        };
+
+        match result {
+            Ok(WorkerState::Busy) => {
+                *service_lock.info().worker_state.write().await = Some(WorkerState::Busy);
+            }
+            Ok(WorkerState::Throttled(delay)) => {
+                *service_lock.info().worker_state.write().await = Some(WorkerState::Busy);
+                drop(service_lock);
+                sleep(delay).await;
+            }
+            Ok(WorkerState::Idle) => {
+                *service_lock.info().worker_state.write().await = Some(WorkerState::Idle);
+                drop(service_lock);
+                sleep(IDLE_BACKOFF).await;
+            }
+            Ok(WorkerState::Done) => {
+                *service_lock.info().worker_state.write().await = Some(WorkerState::Done);
+                break;
+            }
+            Err(error) => {
+                *service_lock.info().worker_state.write().await = None;
+                let mut status = service_lock.info().status.write().await;
+                *status = Status::RuntimeError(
+                    format!("The background task has encountered an error: {}", error),
+                );
+                error!(
+                    "Watchdog triggered for service {}. {}",
+                    service_lock.info().id,
+                    status
+                );
+
+                let restart_delay = service_manager.note_failure(service_lock.info()).await;
+                drop(status);
+                drop(service_lock);
+
+                if let Some(delay) = restart_delay {
+                    spawn(restart_after(
+                        Arc::clone(&service),
+                        Arc::clone(&service_manager),
+                        delay,
+                    ));
+                }
+
+                break;
+            }
+        }
+    }
+}
+
+/// Waits out a restart backoff delay, then re-runs `start()` on the
+/// service, giving it a fresh worker loop via [`spawn_supervised`].
+async fn restart_after<T>(
+    service: Arc<RwLock<T>>,
+    service_manager: Arc<ServiceManager>,
+    delay: Duration,
+) where
+    T: Worker + 'static,
+{
+    sleep(delay).await;
+
+    let mut service_lock = service.write().await;
+    let id = service_lock.info().id;
+
+    match service_lock.start(Arc::clone(&service_manager)).await {
+        Ok(()) => {
+            *service_lock.info().status.write().await = Status::Started;
+        }
+        Err(error) => {
+            *service_lock.info().status.write().await =
+                Status::RuntimeError(format!("Restart attempt failed: {}", error));
+            error!("Restart attempt failed for service {}. {}", id, error);
+        }
+    }
+}
+
+/// Turns a caught panic's payload into a human-readable message.
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic payload".to_string()
+    }
+}
@@ -0,0 +1,57 @@
+use std::{
+    error::Error,
+    fmt::{self, Display, Formatter},
+    sync::OnceLock,
+};
+
+/// A write-once cell: `set` succeeds exactly once, every later call fails
+/// without disturbing the stored value.
+pub struct SetLock<T>(OnceLock<T>);
+
+impl<T> SetLock<T> {
+    pub fn new() -> Self {
+        Self(OnceLock::new())
+    }
+
+    pub fn set(&self, value: T) -> Result<(), SetLockError> {
+        self.0.set(value).map_err(|_| SetLockError)
+    }
+
+    pub fn get(&self) -> Option<&T> {
+        self.0.get()
+    }
+
+    /// Clears any previously set value and stores `value` in its place.
+    /// Takes `&mut self`, unlike `set`, since replacing the backing cell
+    /// while another call might be mid-`get`/`unwrap` through a shared
+    /// reference would be unsound; the exclusive borrow rules that out.
+    /// Useful for state that is write-once *per start*, like a service's
+    /// restart-supervised handles, rather than write-once for its whole
+    /// lifetime.
+    pub fn replace(&mut self, value: T) {
+        self.0 = OnceLock::new();
+        let _ = self.0.set(value);
+    }
+
+    /// Panics if the value has not been set yet.
+    pub fn unwrap(&self) -> &T {
+        self.0.get().expect("SetLock was read before it was set")
+    }
+}
+
+impl<T> Default for SetLock<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug)]
+pub struct SetLockError;
+
+impl Display for SetLockError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "value was already set")
+    }
+}
+
+impl Error for SetLockError {}